@@ -0,0 +1,190 @@
+use crate::error::JudgeCoreError;
+use nix::unistd::Pid;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CGROUP_PARENT: &str = "judger";
+
+/// A per-run cgroup v2 leaf used to cap memory and process count for a
+/// sandboxed child, and to report accurate peak RSS / genuine-OOM afterwards.
+/// This is an alternative to the `setrlimit`-based limits in
+/// `ResourceLimitConfig`: RLIMIT_AS rejects solutions that legitimately
+/// reserve large address space without touching it, and RLIMIT_AS can't
+/// report accurate peak RSS or distinguish an OOM kill from an ordinary
+/// crash. Cumulative CPU time is deliberately left to `RLIMIT_CPU` — see
+/// [`CgroupLimiter::cpu_usage_usec`].
+///
+/// Naming is deterministic (`run-<pid>`, see [`CgroupLimiter::name_for_pid`])
+/// so the process that forks a sandboxed child never has to be handed a live
+/// handle back: it already learns the child's pid from `fork`/`wait`, and can
+/// reopen the same cgroup with [`CgroupLimiter::open`] to read
+/// [`CgroupLimiter::max_memory`] / [`CgroupLimiter::oom_killed`] and then
+/// [`CgroupLimiter::remove`] it. The child only needs to create it, configure
+/// the limits, and [`CgroupLimiter::join`] — it must NOT remove the cgroup
+/// itself, since it is still a member of it until `execve` replaces its image
+/// (removing it pre-`execve` always fails with EBUSY/ENOTEMPTY, and doing so
+/// would also destroy the stats the parent reads after `wait`).
+pub struct CgroupLimiter {
+    path: PathBuf,
+}
+
+impl CgroupLimiter {
+    /// Whether the unified (v2) cgroup hierarchy is mounted and usable.
+    pub fn is_available() -> bool {
+        Path::new(CGROUP_ROOT).join("cgroup.controllers").exists()
+    }
+
+    /// The deterministic leaf name for the cgroup a sandboxed process with
+    /// `pid` would create, so a parent that only has the pid back from
+    /// `wait` can [`CgroupLimiter::open`] the same cgroup without the child
+    /// handing anything back.
+    pub fn name_for_pid(pid: Pid) -> String {
+        format!("run-{pid}")
+    }
+
+    /// Creates `/sys/fs/cgroup/judger/<name>`, failing if cgroup v2 isn't
+    /// mounted or the judger lacks delegation over the unified hierarchy.
+    pub fn new(name: &str) -> Result<Self, JudgeCoreError> {
+        if !Self::is_available() {
+            return Err(JudgeCoreError::Other(
+                "cgroup v2 is not available on this host".to_string(),
+            ));
+        }
+        let path = Path::new(CGROUP_ROOT).join(CGROUP_PARENT).join(name);
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    /// Reopens an already-created cgroup by name, for reading stats or
+    /// removing it once the process that joined it has been reaped. Does not
+    /// create or configure anything, so it never fails even if the cgroup
+    /// turns out not to exist — callers find that out from `max_memory`/
+    /// `oom_killed`/`remove` instead.
+    pub fn open(name: &str) -> Self {
+        Self {
+            path: Path::new(CGROUP_ROOT).join(CGROUP_PARENT).join(name),
+        }
+    }
+
+    pub fn set_memory_limit(&self, bytes: u64) -> Result<(), JudgeCoreError> {
+        self.write_control("memory.max", &bytes.to_string())?;
+        // Disallow falling back to swap so memory.peak reflects a true MLE.
+        self.write_control("memory.swap.max", "0")
+    }
+
+    /// Bounds the *rate* of CPU consumption to `quota_us` out of every
+    /// `period_us` (cgroup v2's `cpu.max`). This throttles concurrency, not
+    /// cumulative time — a single-threaded busy loop only ever wants one
+    /// core, so `quota_us >= period_us` never throttles it no matter how
+    /// long it runs. It is not a substitute for a CPU-time limit; use
+    /// `RLIMIT_CPU` (or poll [`CgroupLimiter::cpu_usage_usec`] against a
+    /// budget) for that.
+    pub fn set_cpu_quota(&self, quota_us: u64, period_us: u64) -> Result<(), JudgeCoreError> {
+        self.write_control("cpu.max", &format!("{quota_us} {period_us}"))
+    }
+
+    pub fn set_pids_max(&self, pids: u64) -> Result<(), JudgeCoreError> {
+        self.write_control("pids.max", &pids.to_string())
+    }
+
+    /// Moves `pid` into this cgroup. Must be called after `fork` and before
+    /// `execve` in the child.
+    pub fn add_process(&self, pid: Pid) -> Result<(), JudgeCoreError> {
+        self.write_control("cgroup.procs", &pid.as_raw().to_string())
+    }
+
+    /// Moves the calling process into this cgroup. Self-assignment (a
+    /// process writing its own pid into `cgroup.procs`) is the normal way
+    /// for a soon-to-be-sandboxed child to join a cgroup right before
+    /// `execve`.
+    pub fn join(&self) -> Result<(), JudgeCoreError> {
+        self.add_process(nix::unistd::getpid())
+    }
+
+    /// Removes this cgroup. Only valid once every process that joined it has
+    /// exited — call after `wait`, never from inside the sandboxed child
+    /// itself (see the struct docs).
+    pub fn remove(&self) -> Result<(), JudgeCoreError> {
+        fs::remove_dir(&self.path)?;
+        Ok(())
+    }
+
+    /// Cumulative CPU time consumed by every process that has ever been a
+    /// member of this cgroup, in microseconds (`cpu.stat`'s `usage_usec`).
+    /// Unlike `cpu.max`, this is monotonically increasing and can be polled
+    /// against a total budget to enforce a real CPU-time limit.
+    pub fn cpu_usage_usec(&self) -> Result<u64, JudgeCoreError> {
+        self.read_control("cpu.stat")?
+            .lines()
+            .find_map(|line| line.strip_prefix("usage_usec "))
+            .and_then(|usec| usec.trim().parse().ok())
+            .ok_or_else(|| JudgeCoreError::Other("failed to parse cpu.stat".to_string()))
+    }
+
+    /// High-water resident memory usage in bytes, more accurate than
+    /// RLIMIT_AS since it reflects what was actually touched.
+    pub fn max_memory(&self) -> Result<u64, JudgeCoreError> {
+        self.read_control("memory.peak")?
+            .trim()
+            .parse()
+            .map_err(|_| JudgeCoreError::Other("failed to parse memory.peak".to_string()))
+    }
+
+    /// Whether the kernel OOM-killed a process in this cgroup, which should
+    /// be reported as a memory-limit verdict rather than a generic crash.
+    pub fn oom_killed(&self) -> Result<bool, JudgeCoreError> {
+        let events = self.read_control("memory.events")?;
+        Ok(events
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))
+            .and_then(|count| count.trim().parse::<u64>().ok())
+            .unwrap_or(0)
+            > 0)
+    }
+
+    fn write_control(&self, file: &str, value: &str) -> Result<(), JudgeCoreError> {
+        fs::write(self.path.join(file), value)?;
+        Ok(())
+    }
+
+    fn read_control(&self, file: &str) -> Result<String, JudgeCoreError> {
+        Ok(fs::read_to_string(self.path.join(file))?)
+    }
+}
+
+#[cfg(test)]
+pub mod cgroup {
+    use super::*;
+
+    // Needs cgroup v2 delegated to the current (test) process, which isn't
+    // guaranteed in every environment this runs in; skip rather than fail
+    // when it's not available, same as `is_available` is used elsewhere to
+    // decide whether the cgroup backend applies at all. Doesn't `join` the
+    // cgroup -- this test process isn't the one being sandboxed, and
+    // `remove` is only valid once no process is a member, matching how
+    // `monitor::read_and_remove_cgroup_report` uses a reopened handle after
+    // the real sandboxed child has already been reaped.
+    #[test]
+    fn test_create_report_remove_roundtrip() {
+        if !CgroupLimiter::is_available() {
+            log::info!("cgroup v2 not available, skipping");
+            return;
+        }
+        let name = CgroupLimiter::name_for_pid(nix::unistd::getpid());
+        let cgroup = match CgroupLimiter::new(&name) {
+            Ok(cgroup) => cgroup,
+            Err(e) => {
+                log::info!("no delegation over cgroup v2, skipping: {:?}", e);
+                return;
+            }
+        };
+        cgroup.set_memory_limit(64 * 1024 * 1024).unwrap();
+
+        let reopened = CgroupLimiter::open(&name);
+        reopened.max_memory().unwrap();
+        assert!(!reopened.oom_killed().unwrap());
+
+        reopened.remove().unwrap();
+    }
+}
@@ -0,0 +1,9 @@
+/// The language a submission is written in, used to pick the right compile
+/// (or interpreter) invocation before judging.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Language {
+    C,
+    Cpp,
+    Java,
+    Python3,
+}
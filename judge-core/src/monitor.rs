@@ -1,6 +1,8 @@
+use crate::cgroup::CgroupLimiter;
 use crate::error::JudgeCoreError;
 use crate::result::{
     check_checker_result, check_user_result, get_max_mem, get_run_time, JudgeResultInfo,
+    JudgeVerdict,
 };
 use crate::sandbox::{ProcessListener, RawRunResultInfo, ResourceLimitConfig, SandBox};
 use nix::errno::Errno;
@@ -8,9 +10,12 @@ use nix::fcntl::{fcntl, FcntlArg, OFlag};
 use nix::sys::epoll::{
     epoll_create1, epoll_ctl, epoll_wait, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
 };
-use nix::unistd::{pipe, read, write};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{pipe, read, write, Pid};
 use std::fs::File;
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 pub struct RunnerConfig {
     pub program_path: String,
@@ -20,6 +25,76 @@ pub struct RunnerConfig {
     pub answer_file_path: String,
     pub check_file_path: String,
     pub rlimit_config: ResourceLimitConfig,
+    /// Wall-clock limit in milliseconds. Enforced independently of
+    /// `rlimit_config`'s CPU time limit, since a program blocked on I/O or
+    /// simply sleeping never accumulates CPU time.
+    pub wall_time_limit_ms: Option<u64>,
+    /// Maximum total bytes a solution may write before it's killed and
+    /// reported as `OutputLimitExceeded`, guarding against an adversarial
+    /// solution flooding the judge's output file.
+    pub output_limit_bytes: Option<u64>,
+}
+
+/// Cancellation handle for a watchdog spawned by
+/// `spawn_wall_clock_watchdog`. The kernel is free to reap and recycle `pid`
+/// well before the watchdog's sleep elapses, so callers MUST cancel the
+/// watchdog as soon as they've reaped the process themselves -- otherwise a
+/// stale timer can fire later and `kill()` an unrelated process that was
+/// assigned the same pid in the meantime.
+struct WallClockWatchdog {
+    cancelled: Arc<AtomicBool>,
+    fired: Arc<AtomicBool>,
+}
+
+impl WallClockWatchdog {
+    /// Disarms the watchdog and reports whether it had already fired (i.e.
+    /// whether `pid` was killed for exceeding the wall-clock limit). Must be
+    /// called right after reaping `pid`, since `check_user_result`'s
+    /// signal/CPU-time based classification can't tell a wall-clock kill
+    /// apart from an ordinary one: a program blocked on I/O or merely
+    /// sleeping shows near-zero CPU time right up until this `SIGKILL`.
+    fn cancel(self) -> bool {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+/// Kills `pid` after `duration_ms` unless cancelled first, used as a
+/// watchdog for the wall-clock limit in contexts (like `run_judge`) that
+/// block on a single `wait()` rather than running their own epoll loop.
+fn spawn_wall_clock_watchdog(pid: Pid, duration_ms: u64) -> WallClockWatchdog {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let fired = Arc::new(AtomicBool::new(false));
+    let watchdog_cancelled = Arc::clone(&cancelled);
+    let watchdog_fired = Arc::clone(&fired);
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(duration_ms));
+        if !watchdog_cancelled.load(Ordering::SeqCst) {
+            watchdog_fired.store(true, Ordering::SeqCst);
+            kill(pid, Signal::SIGKILL).ok();
+        }
+    });
+    WallClockWatchdog { cancelled, fired }
+}
+
+/// Reads and removes the cgroup v2 accounting `runner::set_limit` created
+/// for `pid` (see `CgroupLimiter::name_for_pid`), if cgroup v2 is the
+/// backend it used. Best-effort and not an error when there's nothing to
+/// report: a sandboxed child that fell back to plain `setrlimit`, or a host
+/// without cgroup v2 delegated at all, simply never created one.
+fn read_and_remove_cgroup_report(pid: Pid) -> Option<(u64, bool)> {
+    if !CgroupLimiter::is_available() {
+        return None;
+    }
+    let cgroup = CgroupLimiter::open(&CgroupLimiter::name_for_pid(pid));
+    let report = match (cgroup.max_memory(), cgroup.oom_killed()) {
+        (Ok(max_memory), Ok(oom_killed)) => Some((max_memory, oom_killed)),
+        _ => None,
+    };
+    if let Err(e) = cgroup.remove() {
+        log::warn!("failed to remove cgroup for pid {pid}: {:?}", e);
+    }
+    report
 }
 
 pub fn run_judge(runner_config: &RunnerConfig) -> Result<Option<JudgeResultInfo>, JudgeCoreError> {
@@ -39,12 +114,50 @@ pub fn run_judge(runner_config: &RunnerConfig) -> Result<Option<JudgeResultInfo>
         input_raw_fd,
         output_raw_fd,
     )?;
-    if user_spawn.is_none() {
+    let Some(user_pid) = user_spawn else {
         return Ok(None);
-    }
+    };
+    let watchdog = runner_config
+        .wall_time_limit_ms
+        .map(|ms| spawn_wall_clock_watchdog(user_pid, ms));
     let user_result = user_process.wait()?;
+    // Cancel immediately after reaping the child, keeping the window in
+    // which the watchdog could fire against a recycled pid as short as
+    // possible.
+    let timed_out = watchdog.map(|w| w.cancel()).unwrap_or(false);
     let user_time = get_run_time(&user_result);
-    let max_mem = get_max_mem(&user_result);
+    let mut max_mem = get_max_mem(&user_result);
+    // cgroup v2's memory.peak reflects resident memory actually touched,
+    // more accurate than whatever `get_max_mem` derives from `rlimit_config`
+    // alone, and memory.events' oom_kill counter is the only way to tell a
+    // genuine OOM kill apart from an ordinary crash.
+    let mut oom_killed = false;
+    if let Some((cgroup_max_mem, killed)) = read_and_remove_cgroup_report(user_pid) {
+        max_mem = max_mem.max(cgroup_max_mem);
+        oom_killed = killed;
+    }
+    // The watchdog firing is an explicit signal, not something
+    // `check_user_result` can infer: a process blocked on I/O or simply
+    // sleeping accumulates almost no CPU time, so it wouldn't otherwise be
+    // classified as time-limit-exceeded.
+    if timed_out {
+        return Ok(Some(JudgeResultInfo {
+            verdict: JudgeVerdict::TimeLimitExceeded,
+            time: user_time,
+            memory: max_mem,
+            exit_status: user_result.exit_status,
+            checker_exit_status: 0,
+        }));
+    }
+    if oom_killed {
+        return Ok(Some(JudgeResultInfo {
+            verdict: JudgeVerdict::MemoryLimitExceeded,
+            time: user_time,
+            memory: max_mem,
+            exit_status: user_result.exit_status,
+            checker_exit_status: 0,
+        }));
+    }
     if let Some(verdict) = check_user_result(&user_result) {
         return Ok(Some(JudgeResultInfo {
             verdict,
@@ -94,8 +207,16 @@ fn set_non_blocking(fd: RawFd) -> Result<libc::c_int, JudgeCoreError> {
     }
 }
 
-// write the content of `from` to `to`, record to output
-fn pump_proxy_pipe(from: RawFd, to: RawFd, output: RawFd) {
+// write the content of `from` to `to`, record to output. Returns true once
+// `total_bytes` (accumulated across both proxy directions) exceeds
+// `output_limit_bytes`, signalling the caller to kill both processes.
+fn pump_proxy_pipe(
+    from: RawFd,
+    to: RawFd,
+    output: RawFd,
+    total_bytes: &mut u64,
+    output_limit_bytes: Option<u64>,
+) -> bool {
     let mut buf = [0; 1024];
     loop {
         match read(from, &mut buf) {
@@ -103,10 +224,14 @@ fn pump_proxy_pipe(from: RawFd, to: RawFd, output: RawFd) {
                 log::info!("{} read. {} -> {}", nread, from, to);
                 write(to, &buf[..nread]).ok();
                 write(output, &buf[..nread]).ok();
+                *total_bytes += nread as u64;
+                if output_limit_bytes.is_some_and(|limit| *total_bytes > limit) {
+                    return true;
+                }
             }
             Err(e) => {
                 if e == Errno::EAGAIN || e == Errno::EWOULDBLOCK {
-                    return;
+                    return false;
                 }
                 panic!("failed to read from pipe");
             }
@@ -114,11 +239,40 @@ fn pump_proxy_pipe(from: RawFd, to: RawFd, output: RawFd) {
     }
 }
 
+/// Creates a one-shot, non-blocking timerfd that fires after `duration_ms`,
+/// suitable for registering into the same epoll set as the proxy pipes.
+fn create_timer_fd(duration_ms: u64) -> Result<RawFd, JudgeCoreError> {
+    let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, libc::TFD_NONBLOCK) };
+    if fd < 0 {
+        return Err(JudgeCoreError::NixErrnoWithMsg(
+            Errno::last(),
+            "failed to create timerfd".to_string(),
+        ));
+    }
+    let spec = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: (duration_ms / 1000) as i64,
+            tv_nsec: ((duration_ms % 1000) * 1_000_000) as i64,
+        },
+    };
+    if unsafe { libc::timerfd_settime(fd, 0, &spec, std::ptr::null_mut()) } < 0 {
+        return Err(JudgeCoreError::NixErrnoWithMsg(
+            Errno::last(),
+            "failed to arm timerfd".to_string(),
+        ));
+    }
+    Ok(fd)
+}
+
 pub fn run_interact(
     runner_config: &RunnerConfig,
     interactor_path: &str,
     output_path: &String,
-) -> Result<Option<RawRunResultInfo>, JudgeCoreError> {
+) -> Result<Option<JudgeResultInfo>, JudgeCoreError> {
     fn add_epoll_fd(epoll_fd: RawFd, fd: RawFd) -> Result<(), JudgeCoreError> {
         let mut event = EpollEvent::new(EpollFlags::EPOLLIN, fd as u64);
         match epoll_ctl(epoll_fd, EpollOp::EpollCtlAdd, fd, Some(&mut event)) {
@@ -182,9 +336,9 @@ pub fn run_interact(
         user_write_proxy,
     )?;
 
-    if user_spawn.is_none() {
+    let Some(user_pid) = user_spawn else {
         return Ok(None);
-    }
+    };
 
     let first_args = String::from("");
     let interact_args = vec![
@@ -202,26 +356,61 @@ pub fn run_interact(
         interactor_write_proxy,
     )?;
 
-    if interact_spawn.is_none() {
+    let Some(interact_pid) = interact_spawn else {
         return Ok(None);
-    }
+    };
 
+    let wall_clock_timer_fd = match runner_config.wall_time_limit_ms {
+        Some(ms) => {
+            let timer_fd = create_timer_fd(ms)?;
+            add_epoll_fd(epoll_fd, timer_fd)?;
+            Some(timer_fd)
+        }
+        None => None,
+    };
+
+    let mut total_output_bytes: u64 = 0;
     let mut events = [EpollEvent::empty(); 128];
-    loop {
+    let mut timed_out = false;
+    let mut output_exceeded = false;
+    let mut interactor_exited_first = false;
+    'epoll: loop {
         let num_events = epoll_wait(epoll_fd, &mut events, -1)?;
         log::info!("{} events found!", num_events);
         let mut exited = false;
         for event in events.iter().take(num_events) {
             let fd = event.data() as RawFd;
+            if Some(fd) == wall_clock_timer_fd {
+                log::info!("wall-clock limit reached");
+                timed_out = true;
+                break 'epoll;
+            }
             if fd == user_exit_read || fd == interactor_exit_read {
                 log::info!("{:?} fd exited", fd);
+                interactor_exited_first = fd == interactor_exit_read;
                 exited = true;
                 break;
             }
             if fd == proxy_read_user {
-                pump_proxy_pipe(proxy_read_user, proxy_write_interactor, output_raw_fd);
+                output_exceeded |= pump_proxy_pipe(
+                    proxy_read_user,
+                    proxy_write_interactor,
+                    output_raw_fd,
+                    &mut total_output_bytes,
+                    runner_config.output_limit_bytes,
+                );
             } else if fd == proxy_read_interactor {
-                pump_proxy_pipe(proxy_read_interactor, proxy_write_user, output_raw_fd);
+                output_exceeded |= pump_proxy_pipe(
+                    proxy_read_interactor,
+                    proxy_write_user,
+                    output_raw_fd,
+                    &mut total_output_bytes,
+                    runner_config.output_limit_bytes,
+                );
+            }
+            if output_exceeded {
+                log::info!("output limit exceeded");
+                break 'epoll;
             }
         }
         if exited {
@@ -231,9 +420,63 @@ pub fn run_interact(
 
     log::info!("Epoll finished!");
 
-    // TODO: get result from listener
-    // let _user_result = user_process.wait()?;
-    // let _interact_result = interact_process.wait()?;
+    if timed_out || output_exceeded {
+        kill(user_pid, Signal::SIGKILL).ok();
+        kill(interact_pid, Signal::SIGKILL).ok();
+        // Reap both children -- without this they stay zombies for the rest
+        // of the judger's lifetime, since nothing else ever `wait()`s them.
+        user_process.wait().ok();
+        interact_process.wait().ok();
+        let _ = read_and_remove_cgroup_report(user_pid);
+        return Ok(Some(JudgeResultInfo {
+            verdict: if timed_out {
+                JudgeVerdict::TimeLimitExceeded
+            } else {
+                JudgeVerdict::OutputLimitExceeded
+            },
+            time: 0,
+            memory: 0,
+            exit_status: 0,
+            checker_exit_status: 0,
+        }));
+    }
+
+    let user_result = user_process.wait()?;
+    let interact_result = interact_process.wait()?;
+    log::info!("interactor exited with {:?}", interact_result.exit_status);
+    let user_time = get_run_time(&user_result);
+    let mut max_mem = get_max_mem(&user_result);
+    let mut oom_killed = false;
+    if let Some((cgroup_max_mem, killed)) = read_and_remove_cgroup_report(user_pid) {
+        max_mem = max_mem.max(cgroup_max_mem);
+        oom_killed = killed;
+    }
+    if oom_killed {
+        return Ok(Some(JudgeResultInfo {
+            verdict: JudgeVerdict::MemoryLimitExceeded,
+            time: user_time,
+            memory: max_mem,
+            exit_status: user_result.exit_status,
+            checker_exit_status: 0,
+        }));
+    }
+
+    // If the interactor exited first and closed its end of the pipe, the
+    // user process reading/writing to it may see EPIPE/SIGPIPE as a side
+    // effect rather than a genuine crash, so don't let that shadow whatever
+    // verdict the checker would otherwise give.
+    let broken_pipe_from_interactor = interactor_exited_first && exited_via_sigpipe(&user_result);
+    if !broken_pipe_from_interactor {
+        if let Some(verdict) = check_user_result(&user_result) {
+            return Ok(Some(JudgeResultInfo {
+                verdict,
+                time: user_time,
+                memory: max_mem,
+                exit_status: user_result.exit_status,
+                checker_exit_status: 0,
+            }));
+        }
+    }
 
     let mut checker_process = SandBox::new(false)?;
     // the checker will compare the output of interactor with answer file
@@ -255,7 +498,20 @@ pub fn run_interact(
         return Ok(None);
     }
     let checker_result = checker_process.wait()?;
-    Ok(Some(checker_result))
+    let verdict = check_checker_result(&checker_result);
+    Ok(Some(JudgeResultInfo {
+        verdict,
+        time: user_time,
+        memory: max_mem,
+        exit_status: user_result.exit_status,
+        checker_exit_status: checker_result.exit_status,
+    }))
+}
+
+/// Whether a process's wait status shows it was killed by `SIGPIPE`, which
+/// can happen harmlessly when its interaction partner exits first.
+fn exited_via_sigpipe(result: &RawRunResultInfo) -> bool {
+    libc::WIFSIGNALED(result.exit_status) && libc::WTERMSIG(result.exit_status) == libc::SIGPIPE
 }
 
 #[cfg(test)]
@@ -282,6 +538,8 @@ pub mod monitor {
             answer_file_path: "../tmp/ans".to_owned(),
             check_file_path: "../tmp/check".to_owned(),
             rlimit_config: TEST_CONFIG,
+            wall_time_limit_ms: None,
+            output_limit_bytes: None,
         };
         let result = run_judge(&runner_config);
         assert!(result.is_ok());
@@ -301,6 +559,32 @@ pub mod monitor {
             answer_file_path: "../tmp/ans".to_owned(),
             check_file_path: "../tmp/check".to_owned(),
             rlimit_config: TEST_CONFIG,
+            wall_time_limit_ms: None,
+            output_limit_bytes: None,
+        };
+        let result = run_judge(&runner_config);
+        assert!(result.is_ok());
+        if let Ok(Some(result)) = result {
+            log::info!("{:?}", result);
+            assert_eq!(result.verdict, JudgeVerdict::TimeLimitExceeded);
+        }
+    }
+
+    #[test]
+    fn test_run_judge_wall_clock_tle() {
+        // A wall-clock limit far shorter than `TEST_CONFIG`'s CPU time
+        // rlimit, so the watchdog -- not `check_user_result`'s signal/CPU
+        // classification -- is what has to produce `TimeLimitExceeded` here.
+        let runner_config = RunnerConfig {
+            program_path: "./../test-collection/dist/programs/infinite_loop".to_owned(),
+            checker_path: "./../test-collection/dist/checkers/lcmp".to_owned(),
+            input_file_path: "../tmp/in".to_owned(),
+            output_file_path: "../tmp/out".to_owned(),
+            answer_file_path: "../tmp/ans".to_owned(),
+            check_file_path: "../tmp/check".to_owned(),
+            rlimit_config: TEST_CONFIG,
+            wall_time_limit_ms: Some(100),
+            output_limit_bytes: None,
         };
         let result = run_judge(&runner_config);
         assert!(result.is_ok());
@@ -320,6 +604,8 @@ pub mod monitor {
             answer_file_path: "../tmp/ans".to_owned(),
             check_file_path: "../tmp/check".to_owned(),
             rlimit_config: TEST_CONFIG,
+            wall_time_limit_ms: None,
+            output_limit_bytes: None,
         };
         let result = run_judge(&runner_config);
         assert!(result.is_ok());
@@ -339,6 +625,8 @@ pub mod monitor {
             answer_file_path: "../tmp/ans".to_owned(),
             check_file_path: "../tmp/check".to_owned(),
             rlimit_config: TEST_CONFIG,
+            wall_time_limit_ms: None,
+            output_limit_bytes: None,
         };
         let result = run_interact(
             &runner_config,
@@ -348,6 +636,7 @@ pub mod monitor {
         match result {
             Ok(Some(result)) => {
                 log::info!("{:?}", result);
+                assert_eq!(result.verdict, JudgeVerdict::Accepted);
             }
             Ok(None) => {
                 log::info!("Ignoring this result, for it's from a fork child process");
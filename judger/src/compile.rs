@@ -0,0 +1,85 @@
+use crate::error::JudgerError;
+use judge_core::compiler::Language;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Writes `code` into `workspace` and makes it runnable, returning a single
+/// path that `run_judge`/`run_interact` can `execve` directly with no
+/// further arguments.
+///
+/// C/C++ compile to a native binary named `main`. Java and Python3 have no
+/// single native executable to hand back, so each gets a tiny `main`
+/// launcher script (written alongside the source, made executable) that
+/// `exec`s the real `java`/`python3` invocation.
+pub fn compile_source(
+    language: Language,
+    code: &str,
+    workspace: &Path,
+) -> Result<PathBuf, JudgerError> {
+    match language {
+        Language::C => compile_native(
+            workspace,
+            "main.c",
+            code,
+            &["gcc", "main.c", "-O2", "-o", "main"],
+        ),
+        Language::Cpp => compile_native(
+            workspace,
+            "main.cpp",
+            code,
+            &["g++", "main.cpp", "-O2", "-std=c++17", "-o", "main"],
+        ),
+        Language::Java => {
+            fs::write(workspace.join("Main.java"), code)?;
+            run_command(workspace, &["javac", "Main.java"])?;
+            write_launcher(workspace, "exec java -cp \"$(dirname \"$0\")\" Main \"$@\"\n")
+        }
+        Language::Python3 => {
+            fs::write(workspace.join("main.py"), code)?;
+            write_launcher(
+                workspace,
+                "exec python3 \"$(dirname \"$0\")/main.py\" \"$@\"\n",
+            )
+        }
+    }
+}
+
+fn compile_native(
+    workspace: &Path,
+    src_name: &str,
+    code: &str,
+    compile_cmd: &[&str],
+) -> Result<PathBuf, JudgerError> {
+    fs::write(workspace.join(src_name), code)?;
+    run_command(workspace, compile_cmd)?;
+    Ok(workspace.join("main"))
+}
+
+fn run_command(workspace: &Path, cmd: &[&str]) -> Result<(), JudgerError> {
+    let status = Command::new(cmd[0])
+        .args(&cmd[1..])
+        .current_dir(workspace)
+        .status()?;
+    if !status.success() {
+        return Err(JudgerError::CompileError(format!(
+            "`{}` exited with {:?}",
+            cmd.join(" "),
+            status.code()
+        )));
+    }
+    Ok(())
+}
+
+/// Writes an executable `#!/bin/sh` launcher at `workspace/main` whose body
+/// is `body`, for languages (Java, Python3) with no single native
+/// executable of their own.
+fn write_launcher(workspace: &Path, body: &str) -> Result<PathBuf, JudgerError> {
+    let launcher_path = workspace.join("main");
+    fs::write(&launcher_path, format!("#!/bin/sh\n{body}"))?;
+    let mut perms = fs::metadata(&launcher_path)?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&launcher_path, perms)?;
+    Ok(launcher_path)
+}
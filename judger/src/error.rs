@@ -0,0 +1,30 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum JudgerError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("judge-core error: {0:?}")]
+    JudgeCore(#[from] judge_core::error::JudgeCoreError),
+    #[error("failed to compile submission: {0}")]
+    CompileError(String),
+    #[error(
+        "too many open files (EMFILE) while setting up a sandbox; \
+         raise RLIMIT_NOFILE or lower judge concurrency"
+    )]
+    TooManyOpenFiles,
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Maps a `judge_core` error surfaced while spawning a sandbox into a typed
+/// `JudgerError`, so an exhausted file descriptor table (EMFILE) shows up in
+/// logs as an obvious, actionable cause rather than a generic nix errno.
+pub fn classify_sandbox_error(e: judge_core::error::JudgeCoreError) -> JudgerError {
+    if let judge_core::error::JudgeCoreError::NixErrnoWithMsg(errno, _) = &e {
+        if *errno == nix::errno::Errno::EMFILE {
+            return JudgerError::TooManyOpenFiles;
+        }
+    }
+    JudgerError::from(e)
+}
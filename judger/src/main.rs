@@ -1,4 +1,5 @@
 mod agent;
+mod compile;
 mod error;
 mod handler;
 mod option;
@@ -20,6 +21,7 @@ use worker::JudgeWorker;
 // Use RUN AND DEBUG feature in VSCode
 async fn main() -> std::io::Result<()> {
     let opt = option::load_option();
+    raise_fd_limit(opt.max_open_files);
 
     let maybe_rclone_client = if opt.enable_rclone {
         Some(agent::rclone::RcloneClient::new(
@@ -34,6 +36,8 @@ async fn main() -> std::io::Result<()> {
             platform_uri,
             fetch_task_interval,
             port,
+            default_parallelism,
+            default_seed,
         } => {
             serve(
                 maybe_rclone_client,
@@ -42,6 +46,8 @@ async fn main() -> std::io::Result<()> {
                 platform_uri.clone(),
                 fetch_task_interval,
                 port,
+                default_parallelism,
+                default_seed,
             )
             .await
         }
@@ -49,6 +55,8 @@ async fn main() -> std::io::Result<()> {
             problem_slug,
             language,
             src_path,
+            parallelism,
+            seed,
         } => judge(
             maybe_rclone_client,
             opt.problem_package_bucket,
@@ -56,10 +64,72 @@ async fn main() -> std::io::Result<()> {
             problem_slug,
             language,
             src_path,
+            parallelism,
+            seed,
         ),
     }
 }
 
+/// `run_interact` alone opens four proxy pipes, two exit pipes and an epoll
+/// fd per judge, and the server may drive several judges at once, so the
+/// default soft RLIMIT_NOFILE quickly runs out under load. Raise it to the
+/// hard ceiling (or `target`, if given and lower) at startup.
+fn raise_fd_limit(target: Option<u64>) {
+    use nix::sys::resource::{getrlimit, setrlimit, Resource::RLIMIT_NOFILE};
+
+    match getrlimit(RLIMIT_NOFILE) {
+        Ok((soft, hard)) => match desired_soft_limit(target, soft, hard) {
+            Some(new_soft) => match setrlimit(RLIMIT_NOFILE, new_soft, hard) {
+                Ok(()) => log::info!("raised RLIMIT_NOFILE soft limit from {soft} to {new_soft}"),
+                Err(e) => log::warn!("failed to raise RLIMIT_NOFILE to {new_soft}: {e}"),
+            },
+            None => {
+                log::info!("RLIMIT_NOFILE soft limit is already {soft} (hard ceiling {hard})")
+            }
+        },
+        Err(e) => log::warn!("failed to query RLIMIT_NOFILE: {e}"),
+    }
+}
+
+/// The soft `RLIMIT_NOFILE` to raise to, given the requested `target` (if
+/// any) and the current `soft`/`hard` limits, or `None` if `soft` already
+/// satisfies it. Never exceeds `hard`, since `setrlimit` rejects that.
+fn desired_soft_limit(target: Option<u64>, soft: u64, hard: u64) -> Option<u64> {
+    let new_soft = target.map_or(hard, |t| t.min(hard));
+    if new_soft <= soft {
+        None
+    } else {
+        Some(new_soft)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desired_soft_limit_no_target_raises_to_hard() {
+        assert_eq!(desired_soft_limit(None, 1024, 65536), Some(65536));
+    }
+
+    #[test]
+    fn test_desired_soft_limit_already_satisfied() {
+        assert_eq!(desired_soft_limit(None, 65536, 65536), None);
+        assert_eq!(desired_soft_limit(Some(1024), 2048, 65536), None);
+    }
+
+    #[test]
+    fn test_desired_soft_limit_target_below_hard() {
+        assert_eq!(desired_soft_limit(Some(4096), 1024, 65536), Some(4096));
+    }
+
+    #[test]
+    fn test_desired_soft_limit_target_above_hard_clamps() {
+        assert_eq!(desired_soft_limit(Some(1_000_000), 1024, 65536), Some(65536));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn serve(
     maybe_rclone_client: Option<RcloneClient>,
     problem_package_bucket: String,
@@ -67,6 +137,8 @@ async fn serve(
     platform_uri: String,
     fetch_task_interval: u64,
     port: u16,
+    default_parallelism: usize,
+    default_seed: Option<u64>,
 ) -> std::io::Result<()> {
     let platform_client = platform::PlatformClient::new(platform_uri.clone());
 
@@ -77,7 +149,9 @@ async fn serve(
         problem_package_bucket.clone(),
         problem_package_dir.clone(),
     ) {
-        Ok(worker) => worker,
+        Ok(worker) => worker
+            .with_default_parallelism(default_parallelism)
+            .with_default_seed(default_seed),
         Err(e) => {
             log::error!("Failed to create worker: {:?}", e);
             return Ok(());
@@ -95,6 +169,7 @@ async fn serve(
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 fn judge(
     maybe_rclone_client: Option<RcloneClient>,
     problem_package_bucket: String,
@@ -102,6 +177,8 @@ fn judge(
     problem_slug: String,
     language: judge_core::compiler::Language,
     src_path: std::path::PathBuf,
+    parallelism: usize,
+    seed: Option<u64>,
 ) -> std::io::Result<()> {
     // Read code from src_path
     let code = match fs::read_to_string(src_path) {
@@ -126,7 +203,7 @@ fn judge(
         }
     };
 
-    match worker.run_judge(problem_slug, language, code) {
+    match worker.run_judge_with_options(problem_slug, language, code, parallelism, seed, true) {
         Ok(result) => {
             println!("{:?}", result);
         }
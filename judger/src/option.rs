@@ -0,0 +1,69 @@
+use clap::{Parser, Subcommand};
+use judge_core::compiler::Language;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "judger", about = "Standalone or server-mode code judger")]
+pub struct Opt {
+    #[command(subcommand)]
+    pub cmd: JudgerCommad,
+
+    #[arg(long, default_value_t = false)]
+    pub enable_rclone: bool,
+
+    #[arg(long, default_value = "")]
+    pub rclone_config_path: String,
+
+    #[arg(long)]
+    pub problem_package_bucket: String,
+
+    #[arg(long)]
+    pub problem_package_dir: PathBuf,
+
+    /// Soft RLIMIT_NOFILE target to raise to at startup. Defaults to the
+    /// hard limit, since a single `run_interact` call alone opens close to
+    /// a dozen fds and the server may drive several judges concurrently.
+    #[arg(long)]
+    pub max_open_files: Option<u64>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JudgerCommad {
+    /// Run as an HTTP server, periodically fetching judge tasks from `platform_uri`.
+    Serve {
+        #[arg(long)]
+        platform_uri: String,
+        #[arg(long, default_value_t = 5)]
+        fetch_task_interval: u64,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Default number of test cases to judge concurrently for a fetched
+        /// task that doesn't request a parallelism degree of its own.
+        #[arg(long, default_value_t = 1)]
+        default_parallelism: usize,
+        /// Default seed used to shuffle case order when a fetched task
+        /// doesn't request one of its own.
+        #[arg(long)]
+        default_seed: Option<u64>,
+    },
+    /// Judge a single local submission and print the verdicts.
+    Judge {
+        #[arg(long)]
+        problem_slug: String,
+        #[arg(long)]
+        language: Language,
+        #[arg(long)]
+        src_path: PathBuf,
+        /// Number of test cases to judge concurrently.
+        #[arg(long, default_value_t = 1)]
+        parallelism: usize,
+        /// Seed used to shuffle case order before dispatch; omit to judge
+        /// cases in order of their directory names.
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+pub fn load_option() -> Opt {
+    Opt::parse()
+}
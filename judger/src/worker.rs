@@ -0,0 +1,329 @@
+use crate::agent::platform::PlatformClient;
+use crate::agent::rclone::RcloneClient;
+use crate::compile::compile_source;
+use crate::error::{classify_sandbox_error, JudgerError};
+use judge_core::compiler::Language;
+use judge_core::monitor::{run_judge, RunnerConfig};
+use judge_core::result::{JudgeResultInfo, JudgeVerdict};
+use judge_core::sandbox::ResourceLimitConfig;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+const DEFAULT_WALL_TIME_LIMIT_MS: u64 = 10_000;
+const DEFAULT_OUTPUT_LIMIT_BYTES: u64 = 64 * 1024 * 1024;
+
+struct TestCase {
+    index: usize,
+    input_path: PathBuf,
+    answer_path: PathBuf,
+}
+
+/// Removes the per-run workspace directory on every exit path, including the
+/// `?`-propagated errors from `compile_source`/`list_cases` that would
+/// otherwise leak it.
+struct WorkspaceGuard(PathBuf);
+
+impl Drop for WorkspaceGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub struct JudgeWorker {
+    platform_client: Option<PlatformClient>,
+    #[allow(dead_code)]
+    rclone_client: Option<RcloneClient>,
+    fetch_task_interval: u64,
+    #[allow(dead_code)]
+    problem_package_bucket: String,
+    problem_package_dir: PathBuf,
+    default_parallelism: usize,
+    default_seed: Option<u64>,
+}
+
+impl JudgeWorker {
+    pub fn new(
+        platform_client: Option<PlatformClient>,
+        rclone_client: Option<RcloneClient>,
+        fetch_task_interval: u64,
+        problem_package_bucket: String,
+        problem_package_dir: PathBuf,
+    ) -> Result<Self, JudgerError> {
+        fs::create_dir_all(&problem_package_dir)?;
+        Ok(Self {
+            platform_client,
+            rclone_client,
+            fetch_task_interval,
+            problem_package_bucket,
+            problem_package_dir,
+            default_parallelism: 1,
+            default_seed: None,
+        })
+    }
+
+    pub fn with_default_parallelism(mut self, parallelism: usize) -> Self {
+        self.default_parallelism = parallelism.max(1);
+        self
+    }
+
+    pub fn with_default_seed(mut self, seed: Option<u64>) -> Self {
+        self.default_seed = seed;
+        self
+    }
+
+    /// Polls the platform for the next task every `fetch_task_interval` and
+    /// judges it, falling back to this worker's default parallelism/seed
+    /// when the task doesn't specify its own.
+    pub async fn run(&self) {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(
+                self.fetch_task_interval.max(1),
+            ))
+            .await;
+
+            let Some(platform_client) = &self.platform_client else {
+                log::debug!("judge worker tick, no platform client configured");
+                continue;
+            };
+            let task = match platform_client.fetch_task().await {
+                Ok(Some(task)) => task,
+                Ok(None) => {
+                    log::debug!("judge worker tick, no task available");
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("failed to fetch task: {:?}", e);
+                    continue;
+                }
+            };
+
+            // A task can request its own parallelism/seed (e.g. a rejudge
+            // that must replay the exact case order of the original
+            // submission); fall back to this worker's defaults otherwise.
+            let parallelism = task.parallelism.unwrap_or(self.default_parallelism);
+            let seed = task.seed.or(self.default_seed);
+            let result = self.run_judge_with_options(
+                task.problem_slug.clone(),
+                task.language,
+                task.code,
+                parallelism,
+                seed,
+                true,
+            );
+            match result {
+                Ok(results) => {
+                    if let Err(e) = platform_client.submit_result(&task.id, results).await {
+                        log::error!("failed to submit result for task {}: {:?}", task.id, e);
+                    }
+                }
+                Err(e) => {
+                    log::error!("failed to judge task {}: {:?}", task.id, e);
+                    if let Err(e) = platform_client.submit_error(&task.id, &e).await {
+                        log::error!("failed to submit error for task {}: {:?}", task.id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Judges `code` against every test case of `problem_slug`, using this
+    /// worker's default parallelism degree and seed.
+    pub fn run_judge(
+        &self,
+        problem_slug: String,
+        language: Language,
+        code: String,
+    ) -> Result<Vec<JudgeResultInfo>, JudgerError> {
+        self.run_judge_with_options(
+            problem_slug,
+            language,
+            code,
+            self.default_parallelism,
+            self.default_seed,
+            true,
+        )
+    }
+
+    /// Judges `code` against every test case in the problem package, running
+    /// up to `parallelism` cases at once, each in its own `SandBox`.
+    ///
+    /// When `seed` is set, case order is shuffled with a seeded PRNG before
+    /// dispatch so repeated runs stay reproducible while still spreading
+    /// adversarial load across cases. When `stop_on_first_failure` is set,
+    /// judging stops as soon as a case is not `Accepted`; otherwise every
+    /// case runs, for full feedback.
+    pub fn run_judge_with_options(
+        &self,
+        problem_slug: String,
+        language: Language,
+        code: String,
+        parallelism: usize,
+        seed: Option<u64>,
+        stop_on_first_failure: bool,
+    ) -> Result<Vec<JudgeResultInfo>, JudgerError> {
+        let problem_dir = self.problem_package_dir.join(&problem_slug);
+        let workspace = problem_dir
+            .join("tmp")
+            .join(format!("run-{}-{}", std::process::id(), thread_seq()));
+        fs::create_dir_all(&workspace)?;
+        let _workspace_guard = WorkspaceGuard(workspace.clone());
+
+        let program_path = compile_source(language, &code, &workspace)?;
+        let program_path = Arc::new(program_path.to_string_lossy().into_owned());
+        let checker_path = Arc::new(
+            problem_dir
+                .join("checker")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        let workspace = Arc::new(workspace);
+
+        let mut cases = list_cases(&problem_dir.join("cases"))?;
+        if let Some(seed) = seed {
+            let mut rng = StdRng::seed_from_u64(seed);
+            cases.shuffle(&mut rng);
+        }
+
+        let degree = parallelism.max(1);
+        let mut results = Vec::with_capacity(cases.len());
+        'cases: for batch in cases.chunks(degree) {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|case| {
+                    let program_path = Arc::clone(&program_path);
+                    let checker_path = Arc::clone(&checker_path);
+                    let workspace = Arc::clone(&workspace);
+                    let input_path = case.input_path.clone();
+                    let answer_path = case.answer_path.clone();
+                    let index = case.index;
+                    thread::spawn(move || {
+                        let output_file_path = workspace
+                            .join(format!("out-{index}"))
+                            .to_string_lossy()
+                            .into_owned();
+                        // `run_judge` opens the output file without creating it.
+                        fs::File::create(&output_file_path)?;
+                        let runner_config = RunnerConfig {
+                            program_path: program_path.to_string(),
+                            checker_path: checker_path.to_string(),
+                            input_file_path: input_path.to_string_lossy().into_owned(),
+                            output_file_path,
+                            answer_file_path: answer_path.to_string_lossy().into_owned(),
+                            check_file_path: workspace
+                                .join(format!("check-{index}"))
+                                .to_string_lossy()
+                                .into_owned(),
+                            rlimit_config: ResourceLimitConfig::default(),
+                            wall_time_limit_ms: Some(DEFAULT_WALL_TIME_LIMIT_MS),
+                            output_limit_bytes: Some(DEFAULT_OUTPUT_LIMIT_BYTES),
+                        };
+                        run_judge(&runner_config)
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                let result = handle
+                    .join()
+                    .map_err(|_| JudgerError::Other("judge thread panicked".to_string()))?
+                    .map_err(classify_sandbox_error)?;
+                let Some(result) = result else {
+                    continue;
+                };
+                let should_stop = stop_on_first_failure && result.verdict != JudgeVerdict::Accepted;
+                results.push(result);
+                if should_stop {
+                    break 'cases;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+fn thread_seq() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+fn list_cases(cases_dir: &Path) -> Result<Vec<TestCase>, JudgerError> {
+    let mut entries: Vec<_> = fs::read_dir(cases_dir)?.filter_map(|e| e.ok()).collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    Ok(entries
+        .into_iter()
+        .filter(|e| e.path().is_dir())
+        .enumerate()
+        .map(|(index, entry)| {
+            let dir = entry.path();
+            TestCase {
+                index,
+                input_path: dir.join("input"),
+                answer_path: dir.join("answer"),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub mod worker {
+    use super::*;
+
+    fn worker(problem_package_dir: PathBuf) -> JudgeWorker {
+        JudgeWorker::new(None, None, 0, "unused-bucket".to_owned(), problem_package_dir).unwrap()
+    }
+
+    #[test]
+    fn test_list_cases_sorted_by_file_name() {
+        let dir = std::env::temp_dir().join(format!("judger-worker-test-cases-{}", thread_seq()));
+        fs::create_dir_all(dir.join("2")).unwrap();
+        fs::create_dir_all(dir.join("10")).unwrap();
+        fs::create_dir_all(dir.join("1")).unwrap();
+
+        let cases = list_cases(&dir).unwrap();
+
+        assert_eq!(cases.len(), 3);
+        assert_eq!(cases[0].input_path, dir.join("1").join("input"));
+        assert_eq!(cases[1].input_path, dir.join("10").join("input"));
+        assert_eq!(cases[2].input_path, dir.join("2").join("input"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_run_judge_with_options_cleans_up_workspace_on_compile_error() {
+        let problem_package_dir =
+            std::env::temp_dir().join(format!("judger-worker-test-pkg-{}", thread_seq()));
+        let problem_dir = problem_package_dir.join("bad-problem");
+        fs::create_dir_all(problem_dir.join("cases")).unwrap();
+        let worker = worker(problem_package_dir.clone());
+
+        let result = worker.run_judge_with_options(
+            "bad-problem".to_owned(),
+            Language::C,
+            "this is not valid C source".to_owned(),
+            1,
+            None,
+            true,
+        );
+
+        assert!(result.is_err());
+        let tmp_dir = problem_dir.join("tmp");
+        let leaked = fs::read_dir(&tmp_dir)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false);
+        assert!(
+            !leaked,
+            "a compile failure must not leave a workspace directory behind"
+        );
+
+        fs::remove_dir_all(&problem_package_dir).ok();
+    }
+}